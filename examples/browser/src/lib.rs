@@ -16,11 +16,41 @@ use std::collections::HashMap;
 use std::ops::Deref;
 use std::rc::Rc;
 
+mod worker;
+
+use worker::{PsdCommand, PsdResponse, PsdWorker};
+
+// Re-exported so the JS loader can call (and await) `initThreadPool` before
+// constructing `AppWrapper`. `flatten_layers_rgba_parallel` runs on rayon's
+// global pool, which on wasm32 only has real worker threads once this has
+// resolved — plain `rayon = "1"` with no JS-side pool init cannot do that at
+// all, it would just run everything on the one wasm32 thread the pool was
+// asked to split work across.
+//
+// The JS entry point this crate expects is therefore:
+//
+//   import init, { initThreadPool, AppWrapper } from "./pkg/psd_browser_demo.js";
+//   await init();
+//   await initThreadPool(navigator.hardwareConcurrency);
+//   const app = new AppWrapper();
+//
+// and the wasm needs to be built with the `atomics`/`bulk-memory` target
+// features `wasm-bindgen-rayon` requires, e.g.:
+//
+//   RUSTFLAGS='-C target-feature=+atomics,+bulk-memory' \
+//     rustup run nightly wasm-pack build --target web -- -Z build-std=panic_abort,std
+pub use wasm_bindgen_rayon::init_thread_pool;
+
 #[wasm_bindgen]
 struct App {
     store: Rc<RefCell<Store>>,
     dom_updater: DomUpdater,
     raf_closure_holder: Rc<RefCell<Option<Box<dyn AsRef<JsValue>>>>>,
+    // Owns the parse/flatten work for any PSD loaded after startup behind an
+    // `.await`. The work itself still runs synchronously on this thread (see
+    // `worker.rs`), so a large file still stalls the render loop while it is
+    // parsed or flattened.
+    psd_worker: PsdWorker,
 }
 
 struct Store {
@@ -37,9 +67,37 @@ impl Deref for Store {
 }
 
 struct State {
-    psd: Psd,
+    psd: Rc<Psd>,
     // Layer name, whether or not it is visible
     layer_visibility: HashMap<String, bool>,
+    // The last fully composited frame, kept around so a layer toggle only has
+    // to repaint the rectangle that actually changed instead of the whole
+    // canvas.
+    composited: Vec<u8>,
+    // The region `composited` still needs repainted before it's handed to
+    // the canvas; `None` once `update` has caught up.
+    dirty_rect: Option<DirtyRect>,
+}
+
+// A layer's bounding box, in the same top/left/bottom/right form the PSD
+// stores it in.
+#[derive(Copy, Clone)]
+struct DirtyRect {
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32,
+}
+
+impl DirtyRect {
+    fn union(self, other: DirtyRect) -> DirtyRect {
+        DirtyRect {
+            top: self.top.min(other.top),
+            left: self.left.min(other.left),
+            bottom: self.bottom.max(other.bottom),
+            right: self.right.max(other.right),
+        }
+    }
 }
 
 impl Store {
@@ -52,21 +110,38 @@ impl Store {
 impl State {
     fn msg(&mut self, msg: &Msg) {
         match msg {
-            Msg::ReplacePsd(psd) => {}
+            // Handled by `App::load_psd`, which owns the async parse +
+            // flatten round trip through `PsdWorker` and swaps the result in
+            // once both resolve; nothing to do on the synchronous path.
+            Msg::ReplacePsd => {}
             Msg::SetLayerVisibility(idx, visible) => {
-                let visibility = self
-                    .layer_visibility
-                    .get_mut(self.psd.layer_by_idx(*idx).unwrap().name())
-                    .unwrap();
+                let layer = self.psd.layer_by_idx(*idx).unwrap();
 
+                let visibility = self.layer_visibility.get_mut(layer.name()).unwrap();
                 *visibility = *visible;
+
+                // Pixels outside this rect are untouched by the recomposite
+                // in `App::update`, so the result stays byte-identical to a
+                // full `flatten_layers_rgba_parallel` — just cheaper.
+                let layer_rect = DirtyRect {
+                    top: layer.layer_top(),
+                    left: layer.layer_left(),
+                    bottom: layer.layer_bottom(),
+                    right: layer.layer_right(),
+                };
+
+                self.dirty_rect = Some(match self.dirty_rect {
+                    Some(existing) => existing.union(layer_rect),
+                    None => layer_rect,
+                });
             }
         }
     }
 }
 
 enum Msg {
-    ReplacePsd(Psd),
+    /// A new PSD has finished loading through `App::load_psd`.
+    ReplacePsd,
     /// Set whether or not a layer (by index) should be visible
     SetLayerVisibility(usize, bool),
 }
@@ -119,7 +194,7 @@ impl App {
         console_error_panic_hook::set_once();
 
         let psd = include_bytes!("../demo.psd");
-        let psd = Psd::from_bytes(psd).unwrap();
+        let psd = Rc::new(Psd::from_bytes(psd).unwrap());
 
         let mut layer_visibility = HashMap::new();
         for layer in psd.layers().iter() {
@@ -133,9 +208,15 @@ impl App {
         let app = html! { <div> </div> };
         let mut dom_updater = DomUpdater::new_append_to_mount(app, &body);
 
+        let composited = psd
+            .flatten_layers_rgba_parallel(&|(_idx, layer)| true)
+            .unwrap();
+
         let state = State {
             psd,
             layer_visibility,
+            composited,
+            dirty_rect: None,
         };
 
         let on_msg = None;
@@ -148,6 +229,7 @@ impl App {
             store,
             dom_updater,
             raf_closure_holder: Rc::new(RefCell::new(None)),
+            psd_worker: PsdWorker::new(),
         };
 
         app.update(vdom);
@@ -155,31 +237,94 @@ impl App {
         Ok(app)
     }
 
-    fn update(&mut self, vdom: VirtualNode) -> Result<(), JsValue> {
-        self.dom_updater.update(vdom);
+    // Parses and flattens a newly loaded PSD through `psd_worker`, then swaps
+    // the result into the store and repaints once the parse, layer-list and
+    // flatten replies have all resolved. The work runs on this thread, inside
+    // the futures `psd_worker` hands back. This is the entry point a `<input type="file">` picker
+    // would call into for a user-supplied PSD instead of the bundled demo
+    // file baked in above.
+    pub fn load_psd(app: Rc<RefCell<App>>, bytes: Vec<u8>) {
+        wasm_bindgen_futures::spawn_local(async move {
+            let worker = app.borrow().psd_worker.clone();
+            let store = Rc::clone(&app.borrow().store);
+
+            let psd = match worker.send(PsdCommand::Parse(bytes)).await {
+                Ok(PsdResponse::Parsed(psd)) => psd,
+                Ok(_) => unreachable!("PsdCommand::Parse always replies with Parsed"),
+                Err(err) => {
+                    clog!("failed to parse dropped psd: {:?}", err);
+                    return;
+                }
+            };
+
+            // Refresh the layers panel and every toggle's default state from
+            // the file that was just loaded, not the one `State` still held.
+            let layer_visibility = match worker.send(PsdCommand::GetLayers).await {
+                Ok(PsdResponse::Layers(names)) => {
+                    names.into_iter().map(|name| (name, true)).collect()
+                }
+                Ok(_) => unreachable!("PsdCommand::GetLayers always replies with Layers"),
+                Err(err) => {
+                    clog!("failed to read dropped psd's layers: {:?}", err);
+                    return;
+                }
+            };
+
+            let response = match worker
+                .send(PsdCommand::Flatten(layer_visibility.clone()))
+                .await
+            {
+                Ok(response) => response,
+                Err(err) => {
+                    clog!("failed to flatten dropped psd: {:?}", err);
+                    return;
+                }
+            };
 
-        let psd = &self.store.borrow().psd;
+            if let PsdResponse::Flattened(pixels) = response {
+                let mut store = store.borrow_mut();
+                store.state.psd = psd;
+                store.state.layer_visibility = layer_visibility;
+                store.state.composited = pixels;
+                store.state.dirty_rect = None;
+            }
 
-        let mut psd_pixels = psd
-            .flatten_layers_rgba(&|(idx, layer)| {
-                let layer_visible = *self
-                    .store
-                    .borrow()
-                    .layer_visibility
-                    .get(layer.name())
-                    .unwrap();
+            // Triggers the same `on_msg` -> request_animation_frame repaint
+            // path `SetLayerVisibility` uses.
+            store.borrow_mut().msg(&Msg::ReplacePsd);
+        });
+    }
 
-                layer_visible
-            })
+    #[cfg(not(feature = "webgl"))]
+    fn update(&mut self, vdom: VirtualNode) -> Result<(), JsValue> {
+        self.dom_updater.update(vdom);
+
+        let mut store = self.store.borrow_mut();
+        let State {
+            psd,
+            layer_visibility,
+            composited,
+            dirty_rect,
+        } = &mut store.state;
+
+        // Only the rect touched since the last repaint is recomposited; the
+        // rest of `composited` is left exactly as it was, which is what
+        // keeps this byte-identical to a full flatten while being
+        // O(rect_area * layers) instead of O(width * height * layers).
+        if let Some(rect) = dirty_rect.take() {
+            psd.recomposite_rect_rgba(
+                composited,
+                (rect.top, rect.left, rect.bottom, rect.right),
+                &|(_idx, layer)| *layer_visibility.get(layer.name()).unwrap(),
+            )
             .unwrap();
+        }
 
-        let psd_pixels = Clamped(&mut psd_pixels[..]);
-        let psd_pixels =
-            ImageData::new_with_u8_clamped_array_and_sh(psd_pixels, psd.width(), psd.height())?;
+        let (width, height) = (psd.width(), psd.height());
+        let psd_pixels = Clamped(&mut composited[..]);
+        let psd_pixels = ImageData::new_with_u8_clamped_array_and_sh(psd_pixels, width, height)?;
 
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
-        let body = document.body().unwrap();
+        let document = web_sys::window().unwrap().document().unwrap();
 
         let canvas: HtmlCanvasElement = document
             .get_element_by_id("psd-visual")
@@ -194,6 +339,35 @@ impl App {
 
         Ok(())
     }
+
+    // GPU backend: each layer is uploaded as a texture once (keyed by layer
+    // index) the first time it's seen, and every call after that is one
+    // blend-mode shader draw per visible layer, bottom to top, plus a final
+    // blit to the canvas (the layers are not composited in a single draw),
+    // so toggling a layer never re-uploads the frame the way `put_image_data`
+    // does on the 2D path above.
+    #[cfg(feature = "webgl")]
+    fn update(&mut self, vdom: VirtualNode) -> Result<(), JsValue> {
+        self.dom_updater.update(vdom);
+
+        let store = self.store.borrow();
+        let psd = &store.psd;
+
+        let document = web_sys::window().unwrap().document().unwrap();
+
+        let canvas: HtmlCanvasElement = document
+            .get_element_by_id("psd-visual")
+            .unwrap()
+            .dyn_into()?;
+        let context = canvas
+            .get_context("webgl")?
+            .unwrap()
+            .dyn_into::<web_sys::WebGlRenderingContext>()?;
+
+        psd.composite_gl(&context, &store.layer_visibility)?;
+
+        Ok(())
+    }
 }
 
 struct Renderer {}
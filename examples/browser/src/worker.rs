@@ -0,0 +1,124 @@
+//! A small message-passing handle around the `Psd` the app is currently
+//! displaying, so `App` can `.await` a parse/flatten instead of calling it
+//! inline.
+//!
+//! **This does not move the work off the main thread.** `send` queues the
+//! command onto a microtask via `wasm_bindgen_futures::future_to_promise`,
+//! but `Psd::from_bytes`/`flatten_layers_rgba_parallel` still run
+//! synchronously, on whatever thread polls that future — the main thread,
+//! same as calling them directly. A real hand-off to the global rayon pool
+//! `flatten_layers_rgba_parallel` composites on would need `Psd` to be
+//! `Send` so it can cross into a pool worker and the result can cross back;
+//! with the `webgl` feature on, `Psd::gl_cache` holds `WebGlProgram`/
+//! `WebGlTexture`, which wrap `JsValue` and are not `Send`, so that hand-off
+//! isn't available here. `PsdWorker` exists as the seam a real
+//! `postMessage`-backed worker (or a webgl-free build with an actually
+//! `Send` `Psd`) would slot into — not as a working fix for big-file jank
+//! today.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+use psd::Psd;
+
+/// A request sent to the worker.
+pub enum PsdCommand {
+    /// Parse a PSD from its raw bytes.
+    Parse(Vec<u8>),
+    /// Flatten the currently parsed PSD using the given per-layer visibility.
+    Flatten(HashMap<String, bool>),
+    /// Fetch the currently parsed PSD's layer names, bottom-to-top.
+    GetLayers,
+}
+
+/// The reply to a [`PsdCommand`].
+pub enum PsdResponse {
+    /// The freshly parsed `Psd`, handed back so the caller can refresh its
+    /// own view of layer rects/metadata instead of acting on a stale one.
+    /// `Rc`-wrapped so handing it back doesn't deep-clone every layer's
+    /// pixel buffer on top of the copy `PsdWorker` already keeps.
+    Parsed(Rc<Psd>),
+    Flattened(Vec<u8>),
+    Layers(Vec<String>),
+}
+
+/// Handle to the `Psd` owned by the worker; every method hands back a future
+/// instead of blocking the caller.
+#[derive(Clone)]
+pub struct PsdWorker {
+    psd: Rc<RefCell<Option<Rc<Psd>>>>,
+}
+
+impl PsdWorker {
+    pub fn new() -> Self {
+        PsdWorker {
+            psd: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub async fn send(&self, command: PsdCommand) -> Result<PsdResponse, JsValue> {
+        let psd = Rc::clone(&self.psd);
+
+        // `future_to_promise` only hands data back as a `JsValue`, but the
+        // responses below carry plain Rust data that has no business
+        // round-tripping through JS. We stash it in this slot instead of
+        // boxing it into a raw pointer, so if the returned future is ever
+        // dropped before being polled again (e.g. the caller times out or
+        // its own future is cancelled), the `Rc` just drops normally rather
+        // than leaking whatever was stashed.
+        let slot: Rc<RefCell<Option<PsdResponse>>> = Rc::new(RefCell::new(None));
+        let slot_for_task = Rc::clone(&slot);
+
+        let promise = wasm_bindgen_futures::future_to_promise(async move {
+            let response = match command {
+                PsdCommand::Parse(bytes) => {
+                    let parsed = Psd::from_bytes(&bytes).map_err(|e| {
+                        JsValue::from_str(&format!("failed to parse psd: {}", e))
+                    })?;
+                    let parsed = Rc::new(parsed);
+                    *psd.borrow_mut() = Some(Rc::clone(&parsed));
+                    PsdResponse::Parsed(parsed)
+                }
+                PsdCommand::Flatten(visibility) => {
+                    let psd = psd.borrow();
+                    let psd = psd
+                        .as_ref()
+                        .ok_or_else(|| JsValue::from_str("Flatten sent before Parse"))?;
+
+                    let pixels = psd
+                        .flatten_layers_rgba_parallel(&|(_idx, layer)| {
+                            *visibility.get(layer.name()).unwrap_or(&true)
+                        })
+                        .map_err(|e| JsValue::from_str(&format!("failed to flatten: {}", e)))?;
+
+                    PsdResponse::Flattened(pixels)
+                }
+                PsdCommand::GetLayers => {
+                    let psd = psd.borrow();
+                    let psd = psd
+                        .as_ref()
+                        .ok_or_else(|| JsValue::from_str("GetLayers sent before Parse"))?;
+
+                    let layers = psd
+                        .layers()
+                        .iter()
+                        .map(|layer| layer.name().to_string())
+                        .collect();
+
+                    PsdResponse::Layers(layers)
+                }
+            };
+
+            *slot_for_task.borrow_mut() = Some(response);
+            Ok(JsValue::UNDEFINED)
+        });
+
+        JsFuture::from(promise).await?;
+
+        Ok(slot.borrow_mut().take().expect("resolved promise always fills the slot"))
+    }
+}
@@ -0,0 +1,100 @@
+/// A layer's blend mode, parsed from the 4-char key Photoshop stores
+/// (`norm`, `mul `, `scrn`, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    Difference,
+    /// Any other key, none of which this compositor has a distinct formula
+    /// for (including `smud`, which isn't one of Photoshop's standard blend
+    /// keys); composited as `Normal`.
+    Other([u8; 4]),
+}
+
+impl BlendMode {
+    pub fn from_key(key: [u8; 4]) -> BlendMode {
+        match &key {
+            b"norm" => BlendMode::Normal,
+            b"mul " => BlendMode::Multiply,
+            b"scrn" => BlendMode::Screen,
+            b"over" => BlendMode::Overlay,
+            b"dark" => BlendMode::Darken,
+            b"lite" => BlendMode::Lighten,
+            b"diff" => BlendMode::Difference,
+            _ => BlendMode::Other(key),
+        }
+    }
+
+    /// Blends a single 0-255 color channel: `a` is the layer (source)
+    /// channel, `b` is the backdrop underneath it.
+    pub(crate) fn blend_channel(self, a: u8, b: u8) -> u8 {
+        match self {
+            BlendMode::Normal | BlendMode::Other(_) => a,
+            BlendMode::Multiply => (a as u32 * b as u32 / 255) as u8,
+            BlendMode::Screen => {
+                255 - (((255 - a) as u32 * (255 - b) as u32) / 255) as u8
+            }
+            BlendMode::Overlay => {
+                if b < 128 {
+                    (2 * a as u32 * b as u32 / 255) as u8
+                } else {
+                    255 - (2 * (255 - a) as u32 * (255 - b) as u32 / 255) as u8
+                }
+            }
+            BlendMode::Darken => a.min(b),
+            BlendMode::Lighten => a.max(b),
+            BlendMode::Difference => (a as i32 - b as i32).unsigned_abs() as u8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_keys() {
+        assert_eq!(BlendMode::from_key(*b"norm"), BlendMode::Normal);
+        assert_eq!(BlendMode::from_key(*b"mul "), BlendMode::Multiply);
+        assert_eq!(BlendMode::from_key(*b"scrn"), BlendMode::Screen);
+        assert_eq!(BlendMode::from_key(*b"over"), BlendMode::Overlay);
+        assert_eq!(BlendMode::from_key(*b"dark"), BlendMode::Darken);
+        assert_eq!(BlendMode::from_key(*b"lite"), BlendMode::Lighten);
+        assert_eq!(BlendMode::from_key(*b"diff"), BlendMode::Difference);
+    }
+
+    #[test]
+    fn falls_back_to_normal_for_unrecognized_keys() {
+        assert_eq!(
+            BlendMode::from_key(*b"smud"),
+            BlendMode::Other(*b"smud")
+        );
+        assert_eq!(BlendMode::Other(*b"smud").blend_channel(10, 20), 10);
+    }
+
+    #[test]
+    fn multiply_matches_the_standard_formula() {
+        assert_eq!(
+            BlendMode::Multiply.blend_channel(200, 100),
+            (200u32 * 100 / 255) as u8
+        );
+    }
+
+    #[test]
+    fn screen_matches_the_standard_formula() {
+        let a = 200u32;
+        let b = 100u32;
+        let expected = 255 - ((255 - a) * (255 - b) / 255) as u8;
+        assert_eq!(BlendMode::Screen.blend_channel(200, 100), expected);
+    }
+
+    #[test]
+    fn darken_and_lighten_are_min_and_max() {
+        assert_eq!(BlendMode::Darken.blend_channel(200, 100), 100);
+        assert_eq!(BlendMode::Lighten.blend_channel(200, 100), 200);
+    }
+}
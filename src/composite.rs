@@ -0,0 +1,183 @@
+use rayon::prelude::*;
+
+use crate::blend::BlendMode;
+use crate::error::PsdError;
+use crate::layer::PsdLayer;
+use crate::Psd;
+
+/// Blends `src` through `mode` against the `dst` backdrop and alpha-overs the
+/// result, scaling the source's alpha by the layer's opacity first. Opacity
+/// scales alpha *before* the alpha-over step, same as Photoshop's layer
+/// opacity slider.
+fn blend_over(dst: &mut [u8; 4], src: [u8; 4], opacity: u8, mode: BlendMode) {
+    let src_a = (src[3] as u32 * opacity as u32 / 255) as u8;
+    if src_a == 0 {
+        return;
+    }
+
+    let blended = [
+        mode.blend_channel(src[0], dst[0]),
+        mode.blend_channel(src[1], dst[1]),
+        mode.blend_channel(src[2], dst[2]),
+    ];
+
+    let src_a = src_a as f32 / 255.0;
+    let dst_a = dst[3] as f32 / 255.0;
+    let out_a = src_a + dst_a * (1.0 - src_a);
+
+    if out_a <= 0.0 {
+        *dst = [0, 0, 0, 0];
+        return;
+    }
+
+    for c in 0..3 {
+        let out_c = (blended[c] as f32 * src_a + dst[c] as f32 * dst_a * (1.0 - src_a)) / out_a;
+        dst[c] = out_c.round().clamp(0.0, 255.0) as u8;
+    }
+    dst[3] = (out_a * 255.0).round().clamp(0.0, 255.0) as u8;
+}
+
+/// Composites a single output pixel from every visible layer that covers it,
+/// bottom-to-top. `layers` must already be ordered bottom-to-top (the order
+/// `Psd::layers` returns them in) and pre-filtered to the visible ones.
+fn composite_pixel(row: i32, col: i32, layers: &[(usize, &PsdLayer)]) -> [u8; 4] {
+    let mut pixel = [0u8, 0, 0, 0];
+
+    for (_idx, layer) in layers {
+        if let Some(src) = layer.pixel_at(row, col) {
+            // Fill opacity only applies to the layer's own interior pixels,
+            // same as `opacity` here since layer effects aren't composited
+            // at all, so the two just multiply together.
+            let opacity = (layer.opacity() as u32 * layer.fill_opacity() as u32 / 255) as u8;
+            blend_over(&mut pixel, src, opacity, layer.blend_mode());
+        }
+    }
+
+    pixel
+}
+
+impl Psd {
+    /// Flattens every visible layer into a single `width() * height() * 4`
+    /// RGBA buffer, bottom-to-top, on the current thread.
+    ///
+    /// `filter` decides per-layer visibility; it's called with each layer's
+    /// index (in bottom-to-top order, matching `Psd::layers`) alongside the
+    /// layer itself.
+    pub fn flatten_layers_rgba<F>(&self, filter: &F) -> Result<Vec<u8>, PsdError>
+    where
+        F: Fn((usize, &PsdLayer)) -> bool,
+    {
+        let width = self.width as i32;
+        let height = self.height as i32;
+
+        let visible: Vec<(usize, &PsdLayer)> = self
+            .layers
+            .iter()
+            .enumerate()
+            .filter(|pair| filter((pair.0, pair.1)))
+            .collect();
+
+        let mut buffer = vec![0u8; self.width as usize * self.height as usize * 4];
+
+        for row in 0..height {
+            let row_start = (row * width * 4) as usize;
+            for col in 0..width {
+                let pixel = composite_pixel(row, col, &visible);
+                buffer[row_start + (col * 4) as usize..row_start + (col * 4) as usize + 4]
+                    .copy_from_slice(&pixel);
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Recomposites only `rect` (canvas-space `(top, left, bottom, right)`,
+    /// clamped to the canvas) of an existing `flatten_layers_rgba` buffer in
+    /// place, leaving every pixel outside it untouched. Used to repaint just
+    /// the area a layer visibility toggle can have changed instead of
+    /// reflowing the whole canvas; the result is byte-identical to calling
+    /// `flatten_layers_rgba` from scratch with the same `filter`.
+    ///
+    /// `buffer` must already be `width() * height() * 4` bytes, laid out the
+    /// same way `flatten_layers_rgba`'s return value is.
+    pub fn recomposite_rect_rgba<F>(
+        &self,
+        buffer: &mut [u8],
+        rect: (i32, i32, i32, i32),
+        filter: &F,
+    ) -> Result<(), PsdError>
+    where
+        F: Fn((usize, &PsdLayer)) -> bool,
+    {
+        let width = self.width as i32;
+        let height = self.height as i32;
+
+        let (top, left, bottom, right) = rect;
+        let top = top.max(0);
+        let left = left.max(0);
+        let bottom = bottom.min(height);
+        let right = right.min(width);
+
+        let visible: Vec<(usize, &PsdLayer)> = self
+            .layers
+            .iter()
+            .enumerate()
+            .filter(|pair| filter((pair.0, pair.1)))
+            .collect();
+
+        for row in top..bottom {
+            let row_start = (row * width * 4) as usize;
+            for col in left..right {
+                let pixel = composite_pixel(row, col, &visible);
+                let px_start = row_start + (col * 4) as usize;
+                buffer[px_start..px_start + 4].copy_from_slice(&pixel);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Same result as `flatten_layers_rgba`, but splits the output into
+    /// horizontal row strips and composites each strip on its own rayon
+    /// worker via `par_chunks_mut` — every strip only reads the (immutable)
+    /// layer pixel data and writes its own slice of the buffer, so there's no
+    /// shared mutable state between workers.
+    ///
+    /// Runs on whichever thread pool is already installed as rayon's global
+    /// pool (on wasm, the fixed set of web workers `initThreadPool` spins up;
+    /// natively, rayon's default). Building a fresh `ThreadPoolBuilder` pool
+    /// per call isn't an option here: on wasm it can't actually pull more
+    /// worker threads out of `initThreadPool`'s bounded channel than it was
+    /// built with, and on native it's wasted setup/teardown. Cap the worker
+    /// count once, at startup, via `rayon::ThreadPoolBuilder::build_global`
+    /// (or `initThreadPool(n)` on wasm) instead of per call.
+    pub fn flatten_layers_rgba_parallel<F>(&self, filter: &F) -> Result<Vec<u8>, PsdError>
+    where
+        F: Fn((usize, &PsdLayer)) -> bool + Sync,
+    {
+        let width = self.width as usize;
+        let height = self.height as usize;
+
+        let visible: Vec<(usize, &PsdLayer)> = self
+            .layers
+            .iter()
+            .enumerate()
+            .filter(|pair| filter((pair.0, pair.1)))
+            .collect();
+
+        let mut buffer = vec![0u8; width * height * 4];
+        let row_bytes = width * 4;
+
+        buffer
+            .par_chunks_mut(row_bytes)
+            .enumerate()
+            .for_each(|(row_idx, row)| {
+                for col in 0..width {
+                    let pixel = composite_pixel(row_idx as i32, col as i32, &visible);
+                    row[col * 4..col * 4 + 4].copy_from_slice(&pixel);
+                }
+            });
+
+        Ok(buffer)
+    }
+}
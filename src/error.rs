@@ -0,0 +1,19 @@
+use std::fmt;
+
+/// Errors that can occur while parsing or compositing a PSD.
+#[derive(Debug)]
+pub enum PsdError {
+    /// The byte stream wasn't a well-formed PSD (or used a feature this
+    /// parser doesn't support, e.g. a bit depth other than 8).
+    Parse(String),
+}
+
+impl fmt::Display for PsdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PsdError::Parse(msg) => write!(f, "failed to parse psd: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PsdError {}
@@ -0,0 +1,428 @@
+//! WebGL compositing backend, gated behind the `webgl` feature.
+//!
+//! Mirrors `flatten_layers_rgba`/`_parallel` but keeps the work on the GPU:
+//! each layer's cropped RGBA bytes are uploaded as a texture once (cached on
+//! the `Psd`, keyed by layer index) and every `composite_gl` call after that
+//! is just a blend-mode shader pass per visible layer, bottom to top, so
+//! toggling visibility never re-uploads pixel data that hasn't changed.
+
+use std::collections::HashMap;
+
+use wasm_bindgen::JsValue;
+use web_sys::{WebGlBuffer, WebGlProgram, WebGlRenderingContext as Gl, WebGlShader, WebGlTexture};
+
+use crate::blend::BlendMode;
+use crate::layer::PsdLayer;
+use crate::Psd;
+
+const VERTEX_SHADER: &str = r#"
+attribute vec2 a_position;
+uniform vec4 u_rect; // offset_x, offset_y, scale_x, scale_y, all in clip space
+varying vec2 v_uv;
+void main() {
+    v_uv = a_position * 0.5 + 0.5;
+    vec2 clip = a_position * u_rect.zw + u_rect.xy;
+    gl_Position = vec4(clip, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+varying vec2 v_uv;
+uniform sampler2D u_layer;
+uniform sampler2D u_backdrop;
+uniform vec2 u_resolution;
+uniform float u_opacity;
+uniform int u_mode;
+
+float blend_channel(float a, float b) {
+    if (u_mode == 1) { return a * b; } // Multiply
+    if (u_mode == 2) { return 1.0 - (1.0 - a) * (1.0 - b); } // Screen
+    if (u_mode == 3) { return b < 0.5 ? 2.0 * a * b : 1.0 - 2.0 * (1.0 - a) * (1.0 - b); } // Overlay
+    if (u_mode == 4) { return min(a, b); } // Darken
+    if (u_mode == 5) { return max(a, b); } // Lighten
+    if (u_mode == 6) { return abs(a - b); } // Difference
+    return a; // Normal / unrecognized
+}
+
+void main() {
+    // Layer textures are uploaded with row 0 = the image's top scanline
+    // (PsdLayer::rgba's convention), but WebGL places texture row 0 at v=0,
+    // i.e. the *bottom* of texture space. Flip v for this sample only -
+    // the ping-pong backdrop below is read back via gl_FragCoord, which
+    // already matches the convention it was rendered with.
+    vec4 src = texture2D(u_layer, vec2(v_uv.x, 1.0 - v_uv.y));
+    vec2 screen_uv = gl_FragCoord.xy / u_resolution;
+    vec4 dst = texture2D(u_backdrop, screen_uv);
+
+    float src_a = src.a * u_opacity;
+    vec3 blended = vec3(
+        blend_channel(src.r, dst.r),
+        blend_channel(src.g, dst.g),
+        blend_channel(src.b, dst.b)
+    );
+
+    float out_a = src_a + dst.a * (1.0 - src_a);
+    vec3 out_rgb = out_a > 0.0
+        ? (blended * src_a + dst.rgb * dst.a * (1.0 - src_a)) / out_a
+        : vec3(0.0);
+
+    gl_FragColor = vec4(out_rgb, out_a);
+}
+"#;
+
+const BLIT_FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+varying vec2 v_uv;
+uniform sampler2D u_layer;
+void main() {
+    gl_FragColor = texture2D(u_layer, v_uv);
+}
+"#;
+
+impl BlendMode {
+    /// The `u_mode` the compositing fragment shader switches on; must track
+    /// `blend_channel`'s arms one for one.
+    fn as_gl_mode(self) -> i32 {
+        match self {
+            BlendMode::Normal | BlendMode::Other(_) => 0,
+            BlendMode::Multiply => 1,
+            BlendMode::Screen => 2,
+            BlendMode::Overlay => 3,
+            BlendMode::Darken => 4,
+            BlendMode::Lighten => 5,
+            BlendMode::Difference => 6,
+        }
+    }
+}
+
+/// Everything `composite_gl` caches across calls on the same `Psd`: the
+/// compiled programs, the shared unit-quad buffer, a pair of ping-pong
+/// framebuffer-backed textures sized to the canvas, and one texture per
+/// layer (uploaded lazily, keyed by layer index).
+#[derive(Default)]
+pub(crate) struct GlCache {
+    programs: Option<Programs>,
+    quad: Option<WebGlBuffer>,
+    ping_pong: Option<PingPong>,
+    layer_textures: HashMap<usize, WebGlTexture>,
+}
+
+impl std::fmt::Debug for GlCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GlCache").finish_non_exhaustive()
+    }
+}
+
+impl Clone for GlCache {
+    // GL objects are tied to the context (and canvas size) they were created
+    // against, so a clone of the `Psd` starts with an empty cache rather than
+    // sharing them.
+    fn clone(&self) -> Self {
+        GlCache::default()
+    }
+}
+
+struct Programs {
+    composite: WebGlProgram,
+    blit: WebGlProgram,
+}
+
+struct PingPong {
+    width: i32,
+    height: i32,
+    a: (web_sys::WebGlFramebuffer, WebGlTexture),
+    b: (web_sys::WebGlFramebuffer, WebGlTexture),
+}
+
+fn compile_shader(gl: &Gl, kind: u32, src: &str) -> Result<WebGlShader, JsValue> {
+    let shader = gl
+        .create_shader(kind)
+        .ok_or_else(|| JsValue::from_str("failed to create shader"))?;
+    gl.shader_source(&shader, src);
+    gl.compile_shader(&shader);
+
+    if gl
+        .get_shader_parameter(&shader, Gl::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(shader)
+    } else {
+        let log = gl
+            .get_shader_info_log(&shader)
+            .unwrap_or_else(|| "unknown shader compile error".to_string());
+        Err(JsValue::from_str(&log))
+    }
+}
+
+fn link_program(gl: &Gl, vertex_src: &str, fragment_src: &str) -> Result<WebGlProgram, JsValue> {
+    let vertex = compile_shader(gl, Gl::VERTEX_SHADER, vertex_src)?;
+    let fragment = compile_shader(gl, Gl::FRAGMENT_SHADER, fragment_src)?;
+
+    let program = gl
+        .create_program()
+        .ok_or_else(|| JsValue::from_str("failed to create program"))?;
+    gl.attach_shader(&program, &vertex);
+    gl.attach_shader(&program, &fragment);
+    gl.link_program(&program);
+
+    if gl
+        .get_program_parameter(&program, Gl::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(program)
+    } else {
+        let log = gl
+            .get_program_info_log(&program)
+            .unwrap_or_else(|| "unknown program link error".to_string());
+        Err(JsValue::from_str(&log))
+    }
+}
+
+fn create_render_target(gl: &Gl, width: i32, height: i32) -> Result<(web_sys::WebGlFramebuffer, WebGlTexture), JsValue> {
+    let texture = gl
+        .create_texture()
+        .ok_or_else(|| JsValue::from_str("failed to create texture"))?;
+    gl.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+    gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+        Gl::TEXTURE_2D,
+        0,
+        Gl::RGBA as i32,
+        width,
+        height,
+        0,
+        Gl::RGBA,
+        Gl::UNSIGNED_BYTE,
+        None,
+    )?;
+    gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, Gl::NEAREST as i32);
+    gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::NEAREST as i32);
+    gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE as i32);
+    gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE as i32);
+
+    let framebuffer = gl
+        .create_framebuffer()
+        .ok_or_else(|| JsValue::from_str("failed to create framebuffer"))?;
+    gl.bind_framebuffer(Gl::FRAMEBUFFER, Some(&framebuffer));
+    gl.framebuffer_texture_2d(
+        Gl::FRAMEBUFFER,
+        Gl::COLOR_ATTACHMENT0,
+        Gl::TEXTURE_2D,
+        Some(&texture),
+        0,
+    );
+
+    Ok((framebuffer, texture))
+}
+
+fn upload_layer_texture(gl: &Gl, layer: &PsdLayer) -> Result<WebGlTexture, JsValue> {
+    let texture = gl
+        .create_texture()
+        .ok_or_else(|| JsValue::from_str("failed to create texture"))?;
+    gl.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+    gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+        Gl::TEXTURE_2D,
+        0,
+        Gl::RGBA as i32,
+        layer.width(),
+        layer.height(),
+        0,
+        Gl::RGBA,
+        Gl::UNSIGNED_BYTE,
+        Some(layer.rgba()),
+    )?;
+    gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, Gl::NEAREST as i32);
+    gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::NEAREST as i32);
+    gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE as i32);
+    gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE as i32);
+
+    Ok(texture)
+}
+
+impl Psd {
+    /// Composites every layer `visibility` marks visible directly onto
+    /// `context`'s canvas, bottom to top, entirely on the GPU.
+    ///
+    /// Each layer's pixels are uploaded as a texture the first time this
+    /// `Psd` sees it and reused on every later call; toggling `visibility`
+    /// only changes which cached textures get drawn, not what's uploaded.
+    pub fn composite_gl(
+        &self,
+        context: &Gl,
+        visibility: &HashMap<String, bool>,
+    ) -> Result<(), JsValue> {
+        let mut cache = self.gl_cache.borrow_mut();
+
+        if cache.programs.is_none() {
+            let composite = link_program(context, VERTEX_SHADER, FRAGMENT_SHADER)?;
+            let blit = link_program(context, VERTEX_SHADER, BLIT_FRAGMENT_SHADER)?;
+            cache.programs = Some(Programs { composite, blit });
+        }
+
+        if cache.quad.is_none() {
+            let quad = context
+                .create_buffer()
+                .ok_or_else(|| JsValue::from_str("failed to create buffer"))?;
+            context.bind_buffer(Gl::ARRAY_BUFFER, Some(&quad));
+            let verts: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0];
+            unsafe {
+                let view = js_sys::Float32Array::view(&verts);
+                context.buffer_data_with_array_buffer_view(Gl::ARRAY_BUFFER, &view, Gl::STATIC_DRAW);
+            }
+            cache.quad = Some(quad);
+        }
+
+        let width = self.width as i32;
+        let height = self.height as i32;
+
+        let needs_new_ping_pong = match &cache.ping_pong {
+            Some(pp) => pp.width != width || pp.height != height,
+            None => true,
+        };
+        if needs_new_ping_pong {
+            if let Some(old) = cache.ping_pong.take() {
+                context.delete_framebuffer(Some(&old.a.0));
+                context.delete_texture(Some(&old.a.1));
+                context.delete_framebuffer(Some(&old.b.0));
+                context.delete_texture(Some(&old.b.1));
+            }
+            let a = create_render_target(context, width, height)?;
+            let b = create_render_target(context, width, height)?;
+            cache.ping_pong = Some(PingPong {
+                width,
+                height,
+                a,
+                b,
+            });
+        }
+
+        // Pull owned clones of everything out of `cache` up front so the
+        // loop below is free to take `cache.layer_textures` mutably without
+        // fighting the borrow checker over the rest of the cache.
+        let programs = Programs {
+            composite: cache.programs.as_ref().unwrap().composite.clone(),
+            blit: cache.programs.as_ref().unwrap().blit.clone(),
+        };
+        let quad = cache.quad.as_ref().unwrap().clone();
+        let pp = cache.ping_pong.as_ref().unwrap();
+        let mut front = (pp.a.0.clone(), pp.a.1.clone());
+        let mut back = (pp.b.0.clone(), pp.b.1.clone());
+
+        // Clear the "current" side of the ping-pong pair to transparent
+        // before compositing the bottom-most layer against it.
+        context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&front.0));
+        context.viewport(0, 0, width, height);
+        context.clear_color(0.0, 0.0, 0.0, 0.0);
+        context.clear(Gl::COLOR_BUFFER_BIT);
+
+        context.use_program(Some(&programs.composite));
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&quad));
+        let position_loc = context.get_attrib_location(&programs.composite, "a_position") as u32;
+        context.enable_vertex_attrib_array(position_loc);
+        context.vertex_attrib_pointer_with_i32(position_loc, 2, Gl::FLOAT, false, 0, 0);
+
+        for (idx, layer) in self.layers.iter().enumerate() {
+            if !*visibility.get(layer.name()).unwrap_or(&true) {
+                continue;
+            }
+
+            let texture = match cache.layer_textures.get(&idx) {
+                Some(texture) => texture.clone(),
+                None => {
+                    let texture = upload_layer_texture(context, layer)?;
+                    cache.layer_textures.insert(idx, texture.clone());
+                    texture
+                }
+            };
+
+            // The layer quad below only covers the layer's own rect, so copy
+            // the whole backdrop into `back` first; every pixel outside that
+            // rect then carries over unchanged.
+            context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&front.0));
+            context.bind_texture(Gl::TEXTURE_2D, Some(&back.1));
+            context.copy_tex_sub_image_2d(Gl::TEXTURE_2D, 0, 0, 0, 0, 0, width, height);
+
+            context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&back.0));
+            context.viewport(0, 0, width, height);
+
+            context.use_program(Some(&programs.composite));
+            context.active_texture(Gl::TEXTURE0);
+            context.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+            context.uniform1i(
+                context.get_uniform_location(&programs.composite, "u_layer").as_ref(),
+                0,
+            );
+            context.active_texture(Gl::TEXTURE1);
+            context.bind_texture(Gl::TEXTURE_2D, Some(&front.1));
+            context.uniform1i(
+                context
+                    .get_uniform_location(&programs.composite, "u_backdrop")
+                    .as_ref(),
+                1,
+            );
+            context.uniform2f(
+                context
+                    .get_uniform_location(&programs.composite, "u_resolution")
+                    .as_ref(),
+                width as f32,
+                height as f32,
+            );
+            // Matches `composite_pixel`'s CPU path: fill opacity only
+            // affects the layer's own interior pixels, same as `opacity`
+            // here since layer effects aren't composited at all, so they
+            // just multiply together.
+            let opacity = layer.opacity() as f32 / 255.0 * (layer.fill_opacity() as f32 / 255.0);
+            context.uniform1f(
+                context.get_uniform_location(&programs.composite, "u_opacity").as_ref(),
+                opacity,
+            );
+            context.uniform1i(
+                context.get_uniform_location(&programs.composite, "u_mode").as_ref(),
+                layer.blend_mode().as_gl_mode(),
+            );
+
+            // Position the unit quad at this layer's rect, in clip space.
+            let clip_left = (layer.layer_left() as f32 / width as f32) * 2.0 - 1.0;
+            let clip_right = (layer.layer_right() as f32 / width as f32) * 2.0 - 1.0;
+            // Canvas rows grow downward; clip space grows upward.
+            let clip_top = 1.0 - (layer.layer_top() as f32 / height as f32) * 2.0;
+            let clip_bottom = 1.0 - (layer.layer_bottom() as f32 / height as f32) * 2.0;
+            context.uniform4f(
+                context.get_uniform_location(&programs.composite, "u_rect").as_ref(),
+                (clip_left + clip_right) / 2.0,
+                (clip_top + clip_bottom) / 2.0,
+                (clip_right - clip_left) / 2.0,
+                (clip_top - clip_bottom) / 2.0,
+            );
+
+            context.draw_arrays(Gl::TRIANGLE_STRIP, 0, 4);
+
+            std::mem::swap(&mut front, &mut back);
+        }
+
+        // `front` now holds the fully composited frame; blit it to the
+        // default framebuffer (the visible canvas).
+        context.bind_framebuffer(Gl::FRAMEBUFFER, None);
+        context.viewport(0, 0, width, height);
+        context.use_program(Some(&programs.blit));
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&quad));
+        let position_loc = context.get_attrib_location(&programs.blit, "a_position") as u32;
+        context.enable_vertex_attrib_array(position_loc);
+        context.vertex_attrib_pointer_with_i32(position_loc, 2, Gl::FLOAT, false, 0, 0);
+        context.active_texture(Gl::TEXTURE0);
+        context.bind_texture(Gl::TEXTURE_2D, Some(&front.1));
+        context.uniform1i(context.get_uniform_location(&programs.blit, "u_layer").as_ref(), 0);
+        context.uniform4f(
+            context.get_uniform_location(&programs.blit, "u_rect").as_ref(),
+            0.0,
+            0.0,
+            1.0,
+            1.0,
+        );
+        context.draw_arrays(Gl::TRIANGLE_STRIP, 0, 4);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::blend::BlendMode;
+
+/// A single layer's pixel data, decoded to straight-alpha RGBA and cropped to
+/// the layer's own bounding box (the box the PSD format stores per layer,
+/// not the full canvas).
+#[derive(Debug, Clone)]
+pub struct PsdLayer {
+    pub(crate) name: String,
+    pub(crate) top: i32,
+    pub(crate) left: i32,
+    pub(crate) bottom: i32,
+    pub(crate) right: i32,
+    /// 0-255, the layer panel opacity slider (separate from per-pixel alpha).
+    pub(crate) opacity: u8,
+    /// 0-255, the layer panel's "Fill" opacity (the `iOpa` additional layer
+    /// info block); distinct from `opacity` in that Photoshop applies it
+    /// only to the layer's own interior pixels, not layer-effects like
+    /// strokes or glows. This crate doesn't composite layer effects at all,
+    /// so it's just folded into `opacity` when blending.
+    pub(crate) fill_opacity: u8,
+    /// The raw 4-char blend mode signature key (`norm`, `mul `, ...) as
+    /// stored in the file.
+    pub(crate) blend_key: [u8; 4],
+    /// `width() * height() * 4` bytes, row-major, straight alpha.
+    pub(crate) rgba: Vec<u8>,
+}
+
+impl PsdLayer {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn layer_top(&self) -> i32 {
+        self.top
+    }
+
+    pub fn layer_left(&self) -> i32 {
+        self.left
+    }
+
+    pub fn layer_bottom(&self) -> i32 {
+        self.bottom
+    }
+
+    pub fn layer_right(&self) -> i32 {
+        self.right
+    }
+
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    pub fn fill_opacity(&self) -> u8 {
+        self.fill_opacity
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        BlendMode::from_key(self.blend_key)
+    }
+
+    /// The raw RGBA bytes for this layer, cropped to its own bounding box.
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// Looks up this layer's pixel at the given canvas-space coordinates,
+    /// returning `None` if they fall outside the layer's bounding box.
+    pub(crate) fn pixel_at(&self, canvas_row: i32, canvas_col: i32) -> Option<[u8; 4]> {
+        if canvas_row < self.top
+            || canvas_row >= self.bottom
+            || canvas_col < self.left
+            || canvas_col >= self.right
+        {
+            return None;
+        }
+
+        let row = (canvas_row - self.top) as usize;
+        let col = (canvas_col - self.left) as usize;
+        let width = self.width() as usize;
+        let idx = (row * width + col) * 4;
+
+        Some([
+            self.rgba[idx],
+            self.rgba[idx + 1],
+            self.rgba[idx + 2],
+            self.rgba[idx + 3],
+        ])
+    }
+}
@@ -0,0 +1,225 @@
+//! Parses Adobe Photoshop `.psd` files and composites their layers into flat
+//! RGBA images.
+
+mod blend;
+mod composite;
+mod error;
+#[cfg(feature = "webgl")]
+mod gl;
+mod layer;
+mod parse;
+
+pub use blend::BlendMode;
+pub use error::PsdError;
+pub use layer::PsdLayer;
+
+/// A parsed PSD: its canvas dimensions and its layers, bottom-to-top in the
+/// same order Photoshop's layer stack (and the file itself) stores them.
+#[derive(Debug, Clone)]
+pub struct Psd {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) layers: Vec<PsdLayer>,
+    /// Cached GL state for `composite_gl` (compiled shaders, the ping-pong
+    /// render targets, one texture per layer); empty until the first call.
+    #[cfg(feature = "webgl")]
+    pub(crate) gl_cache: std::cell::RefCell<gl::GlCache>,
+}
+
+impl Psd {
+    /// Parses a PSD from its raw bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Psd, PsdError> {
+        parse::parse(bytes)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// All layers, bottom-to-top.
+    pub fn layers(&self) -> &[PsdLayer] {
+        &self.layers
+    }
+
+    pub fn layer_by_idx(&self, idx: usize) -> Option<&PsdLayer> {
+        self.layers.get(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(name: &str, rect: (i32, i32, i32, i32), rgba: [u8; 4]) -> PsdLayer {
+        let (top, left, bottom, right) = rect;
+        let width = (right - left) as usize;
+        let height = (bottom - top) as usize;
+
+        PsdLayer {
+            name: name.to_string(),
+            top,
+            left,
+            bottom,
+            right,
+            opacity: 255,
+            fill_opacity: 255,
+            blend_key: *b"norm",
+            rgba: rgba.repeat(width * height),
+        }
+    }
+
+    fn psd(width: u32, height: u32, layers: Vec<PsdLayer>) -> Psd {
+        Psd {
+            width,
+            height,
+            layers,
+            #[cfg(feature = "webgl")]
+            gl_cache: std::cell::RefCell::new(Default::default()),
+        }
+    }
+
+    #[test]
+    fn flattens_a_single_opaque_layer() {
+        let psd = psd(2, 2, vec![layer("a", (0, 0, 2, 2), [10, 20, 30, 255])]);
+
+        let flattened = psd.flatten_layers_rgba(&|_| true).unwrap();
+
+        assert_eq!(flattened, [10, 20, 30, 255].repeat(4));
+    }
+
+    #[test]
+    fn hides_layers_the_filter_rejects() {
+        let psd = psd(
+            1,
+            1,
+            vec![
+                layer("bottom", (0, 0, 1, 1), [10, 10, 10, 255]),
+                layer("top", (0, 0, 1, 1), [200, 200, 200, 255]),
+            ],
+        );
+
+        let flattened = psd.flatten_layers_rgba(&|(idx, _)| idx != 1).unwrap();
+
+        assert_eq!(flattened, [10, 10, 10, 255]);
+    }
+
+    #[test]
+    fn stacks_layers_bottom_to_top() {
+        let psd = psd(
+            1,
+            1,
+            vec![
+                layer("bottom", (0, 0, 1, 1), [255, 0, 0, 255]),
+                layer("top", (0, 0, 1, 1), [0, 255, 0, 255]),
+            ],
+        );
+
+        let flattened = psd.flatten_layers_rgba(&|_| true).unwrap();
+
+        assert_eq!(flattened, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn applies_blend_mode_and_opacity_before_alpha_over() {
+        let bottom = layer("bottom", (0, 0, 1, 1), [100, 100, 100, 255]);
+        let mut top = layer("top", (0, 0, 1, 1), [200, 200, 200, 255]);
+        top.blend_key = *b"mul ";
+        top.opacity = 128; // ~50%
+
+        let psd = psd(1, 1, vec![bottom, top]);
+        let flattened = psd.flatten_layers_rgba(&|_| true).unwrap();
+
+        // Multiply(200, 100) = 200*100/255 = 78, then alpha-over at ~50%
+        // opacity against the 100 backdrop.
+        let multiplied = (200u32 * 100 / 255) as u8;
+        let src_a = (255u32 * 128 / 255) as f32 / 255.0;
+        let expected = (multiplied as f32 * src_a + 100.0 * (1.0 - src_a)).round() as u8;
+
+        assert_eq!(flattened[0], expected);
+    }
+
+    #[test]
+    fn fill_opacity_multiplies_with_layer_opacity() {
+        let bottom = layer("bottom", (0, 0, 1, 1), [100, 100, 100, 255]);
+        let mut top = layer("top", (0, 0, 1, 1), [200, 200, 200, 255]);
+        top.opacity = 200;
+        top.fill_opacity = 128; // ~50%, distinct from layer opacity
+
+        let psd = psd(1, 1, vec![bottom, top]);
+        let flattened = psd.flatten_layers_rgba(&|_| true).unwrap();
+
+        let combined_opacity = (200u32 * 128 / 255) as u8;
+        let src_a = combined_opacity as f32 / 255.0;
+        let expected = (200.0 * src_a + 100.0 * (1.0 - src_a)).round() as u8;
+
+        assert_eq!(flattened[0], expected);
+    }
+
+    #[test]
+    fn parallel_flatten_matches_serial_flatten() {
+        let psd = psd(
+            4,
+            3,
+            vec![
+                layer("bottom", (0, 0, 3, 4), [40, 40, 40, 200]),
+                layer("top", (1, 1, 3, 3), [10, 200, 10, 128]),
+            ],
+        );
+
+        let serial = psd.flatten_layers_rgba(&|_| true).unwrap();
+        let parallel = psd.flatten_layers_rgba_parallel(&|_| true).unwrap();
+
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn recompositing_the_dirty_rect_matches_a_full_reflatten() {
+        let psd = psd(
+            4,
+            3,
+            vec![
+                layer("bottom", (0, 0, 3, 4), [40, 40, 40, 200]),
+                layer("top", (1, 1, 3, 3), [10, 200, 10, 128]),
+            ],
+        );
+
+        // Start from the fully visible flatten, then hide "top" and
+        // recomposite only its bounding box, the same rect a toggle in the
+        // example app would mark dirty.
+        let mut buffer = psd.flatten_layers_rgba(&|_| true).unwrap();
+        let hide_top = |(idx, _): (usize, &PsdLayer)| idx != 1;
+
+        psd.recomposite_rect_rgba(&mut buffer, (1, 1, 3, 3), &hide_top)
+            .unwrap();
+
+        let expected = psd.flatten_layers_rgba(&hide_top).unwrap();
+        assert_eq!(buffer, expected);
+    }
+
+    #[test]
+    fn recompositing_leaves_pixels_outside_the_rect_untouched() {
+        let psd = psd(
+            4,
+            3,
+            vec![
+                layer("bottom", (0, 0, 3, 4), [40, 40, 40, 200]),
+                layer("top", (1, 1, 3, 3), [10, 200, 10, 128]),
+            ],
+        );
+
+        let mut buffer = psd.flatten_layers_rgba(&|_| true).unwrap();
+        let before = buffer.clone();
+
+        // Recompositing an empty rect with "top" hidden should leave every
+        // pixel exactly as it was, including the ones "top" still covers in
+        // the buffer, since none of them lie inside the rect.
+        psd.recomposite_rect_rgba(&mut buffer, (0, 0, 0, 0), &|(idx, _)| idx != 1)
+            .unwrap();
+
+        assert_eq!(buffer, before);
+    }
+}
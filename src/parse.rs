@@ -0,0 +1,681 @@
+use crate::error::PsdError;
+use crate::layer::PsdLayer;
+use crate::Psd;
+
+/// A forward-only cursor over the PSD byte stream; every PSD integer field
+/// is big-endian ("Motorola" in Adobe's spec language).
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], PsdError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| PsdError::Parse("unexpected end of file".into()))?;
+
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<(), PsdError> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, PsdError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, PsdError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, PsdError> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, PsdError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, PsdError> {
+        Ok(self.u32()? as i32)
+    }
+}
+
+struct ChannelRecord {
+    id: i16,
+    len: u32,
+}
+
+struct LayerRecord {
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32,
+    channels: Vec<ChannelRecord>,
+    blend_key: [u8; 4],
+    opacity: u8,
+    fill_opacity: u8,
+    name: String,
+}
+
+pub(crate) fn parse(bytes: &[u8]) -> Result<Psd, PsdError> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.take(4)? != b"8BPS" {
+        return Err(PsdError::Parse("missing '8BPS' signature".into()));
+    }
+    if reader.u16()? != 1 {
+        return Err(PsdError::Parse("only PSD version 1 is supported".into()));
+    }
+    reader.skip(6)?; // reserved
+
+    let _channels = reader.u16()?;
+    let height = reader.u32()?;
+    let width = reader.u32()?;
+    let depth = reader.u16()?;
+    let _color_mode = reader.u16()?;
+
+    if depth != 8 {
+        return Err(PsdError::Parse(format!(
+            "only 8-bit channel depth is supported, got {}",
+            depth
+        )));
+    }
+
+    let color_mode_data_len = reader.u32()? as usize;
+    reader.skip(color_mode_data_len)?;
+
+    let image_resources_len = reader.u32()? as usize;
+    reader.skip(image_resources_len)?;
+
+    let layer_mask_info_len = reader.u32()? as usize;
+
+    // Nothing after the layer records (global layer mask info, merged image
+    // data) is needed to composite layers, so we don't bother tracking where
+    // this section ends.
+    let layers = if layer_mask_info_len == 0 {
+        Vec::new()
+    } else {
+        let _layer_info_len = reader.u32()? as usize;
+        let layer_count = reader.i16()?.unsigned_abs() as usize;
+
+        let mut records = Vec::with_capacity(layer_count);
+        for _ in 0..layer_count {
+            records.push(parse_layer_record(&mut reader)?);
+        }
+
+        records
+            .into_iter()
+            .map(|record| decode_layer(&mut reader, record))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    Ok(Psd {
+        width,
+        height,
+        layers,
+        #[cfg(feature = "webgl")]
+        gl_cache: std::cell::RefCell::new(Default::default()),
+    })
+}
+
+fn parse_layer_record(reader: &mut Reader) -> Result<LayerRecord, PsdError> {
+    let top = reader.i32()?;
+    let left = reader.i32()?;
+    let bottom = reader.i32()?;
+    let right = reader.i32()?;
+
+    let channel_count = reader.u16()? as usize;
+    let mut channels = Vec::with_capacity(channel_count);
+    for _ in 0..channel_count {
+        let id = reader.i16()?;
+        let len = reader.u32()?;
+        channels.push(ChannelRecord { id, len });
+    }
+
+    if reader.take(4)? != b"8BIM" {
+        return Err(PsdError::Parse("missing layer blend mode signature".into()));
+    }
+    let mut blend_key = [0u8; 4];
+    blend_key.copy_from_slice(reader.take(4)?);
+
+    let opacity = reader.u8()?;
+    let _clipping = reader.u8()?;
+    let _flags = reader.u8()?;
+    let _filler = reader.u8()?;
+
+    let extra_data_len = reader.u32()? as usize;
+    let extra_data_end = reader.pos + extra_data_len;
+
+    let layer_mask_data_len = reader.u32()? as usize;
+    reader.skip(layer_mask_data_len)?;
+
+    let blending_ranges_len = reader.u32()? as usize;
+    reader.skip(blending_ranges_len)?;
+
+    let name = parse_pascal_string(reader)?;
+
+    // Additional layer info blocks (layer effects, text, fill opacity, etc.)
+    // follow the name; we only care about `iOpa` (fill opacity) to
+    // composite pixels, so everything else is skipped wholesale.
+    let fill_opacity = parse_fill_opacity(reader, extra_data_end)?;
+    reader.pos = extra_data_end;
+
+    Ok(LayerRecord {
+        top,
+        left,
+        bottom,
+        right,
+        channels,
+        blend_key,
+        opacity,
+        fill_opacity,
+        name,
+    })
+}
+
+/// Scans the additional layer info blocks between the current position and
+/// `extra_data_end` for the `iOpa` (fill opacity) key, defaulting to fully
+/// opaque (255) if it's absent. Each block is `signature(4, "8BIM") + key(4) +
+/// length(4) + data(length, padded to an even byte count)`.
+///
+/// `"8B64"`-signed blocks (an 8-byte length field instead of 4) only show up
+/// in the large-document (PSB) format, which `parse` already rejects via its
+/// version-1-only check, so they're treated the same as any other malformed
+/// signature here: stop scanning rather than misreading the length.
+fn parse_fill_opacity(reader: &mut Reader, extra_data_end: usize) -> Result<u8, PsdError> {
+    let mut fill_opacity = 255u8;
+
+    while reader.pos + 12 <= extra_data_end {
+        let signature = reader.take(4)?;
+        if signature != b"8BIM" {
+            // Not a well-formed additional layer info block; stop scanning
+            // and let the caller's `reader.pos = extra_data_end` skip past
+            // whatever's left instead of misinterpreting it as more blocks.
+            break;
+        }
+
+        let mut key = [0u8; 4];
+        key.copy_from_slice(reader.take(4)?);
+        let len = reader.u32()? as usize;
+        let padded_len = len + (len % 2);
+
+        // A length that would run past this layer's own additional-info
+        // region can't belong to a real block here; stop rather than reading
+        // into whatever follows (the next layer's record, or pixel data).
+        if reader.pos + padded_len > extra_data_end {
+            break;
+        }
+
+        if &key == b"iOpa" && len >= 1 {
+            fill_opacity = reader.u8()?;
+            reader.skip(padded_len - 1)?;
+        } else {
+            reader.skip(padded_len)?;
+        }
+    }
+
+    Ok(fill_opacity)
+}
+
+/// A Pascal string (length byte + bytes), padded so the *whole* field
+/// (length byte included) is a multiple of 4 bytes.
+fn parse_pascal_string(reader: &mut Reader) -> Result<String, PsdError> {
+    let len = reader.u8()? as usize;
+    let bytes = reader.take(len)?.to_vec();
+
+    let padded_total = (len + 1).div_ceil(4) * 4;
+    reader.skip(padded_total - (len + 1))?;
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn decode_layer(reader: &mut Reader, record: LayerRecord) -> Result<PsdLayer, PsdError> {
+    let width = (record.right - record.left).max(0) as usize;
+    let height = (record.bottom - record.top).max(0) as usize;
+
+    let mut red = vec![0u8; width * height];
+    let mut green = vec![0u8; width * height];
+    let mut blue = vec![0u8; width * height];
+    let mut alpha = vec![255u8; width * height];
+
+    for channel in &record.channels {
+        if channel.len == 0 {
+            continue;
+        }
+
+        // Color/alpha channels share the layer's own bounding box; any other
+        // channel (e.g. a layer mask, id -2) has its own unrelated
+        // dimensions we don't need, so we just skip its bytes wholesale.
+        if !matches!(channel.id, -1..=2) {
+            reader.skip(channel.len as usize)?;
+            continue;
+        }
+
+        // `len` covers the compression u16 plus the channel's data, so
+        // anything shorter than that is a malformed file, not valid input
+        // with nothing to decode.
+        if channel.len < 2 {
+            return Err(PsdError::Parse("channel data shorter than its own header".into()));
+        }
+
+        let compression = reader.u16()?;
+        let data = reader.take(channel.len as usize - 2)?;
+        let plane = decode_channel_plane(data, compression, width, height)?;
+
+        match channel.id {
+            0 => red = plane,
+            1 => green = plane,
+            2 => blue = plane,
+            -1 => alpha = plane,
+            _ => unreachable!(),
+        }
+    }
+
+    let mut rgba = vec![0u8; width * height * 4];
+    for i in 0..width * height {
+        rgba[i * 4] = red[i];
+        rgba[i * 4 + 1] = green[i];
+        rgba[i * 4 + 2] = blue[i];
+        rgba[i * 4 + 3] = alpha[i];
+    }
+
+    Ok(PsdLayer {
+        name: record.name,
+        top: record.top,
+        left: record.left,
+        bottom: record.bottom,
+        right: record.right,
+        opacity: record.opacity,
+        fill_opacity: record.fill_opacity,
+        blend_key: record.blend_key,
+        rgba,
+    })
+}
+
+fn decode_channel_plane(
+    data: &[u8],
+    compression: u16,
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, PsdError> {
+    match compression {
+        0 => {
+            if data.len() < width * height {
+                return Err(PsdError::Parse("raw channel data is too short".into()));
+            }
+            Ok(data[..width * height].to_vec())
+        }
+        1 => decode_packbits_plane(data, width, height),
+        other => Err(PsdError::Parse(format!(
+            "unsupported channel compression {}",
+            other
+        ))),
+    }
+}
+
+/// Adobe's PackBits variant: `height` big-endian row byte-counts up front,
+/// then the compressed rows back to back.
+fn decode_packbits_plane(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, PsdError> {
+    let mut reader = Reader::new(data);
+
+    let mut row_lens = Vec::with_capacity(height);
+    for _ in 0..height {
+        row_lens.push(reader.u16()? as usize);
+    }
+
+    let mut out = Vec::with_capacity(width * height);
+    for row_len in row_lens {
+        let row = reader.take(row_len)?;
+        decode_packbits_row(row, width, &mut out)?;
+    }
+
+    Ok(out)
+}
+
+fn decode_packbits_row(data: &[u8], width: usize, out: &mut Vec<u8>) -> Result<(), PsdError> {
+    let start_len = out.len();
+    let mut i = 0;
+
+    while out.len() - start_len < width {
+        let control = *data
+            .get(i)
+            .ok_or_else(|| PsdError::Parse("packbits row ended early".into()))? as i8;
+        i += 1;
+
+        if control >= 0 {
+            let count = control as usize + 1;
+            let bytes = data
+                .get(i..i + count)
+                .ok_or_else(|| PsdError::Parse("packbits literal run overruns row".into()))?;
+            out.extend_from_slice(bytes);
+            i += count;
+        } else if control != -128 {
+            let count = (1 - control as i32) as usize;
+            let byte = *data
+                .get(i)
+                .ok_or_else(|| PsdError::Parse("packbits repeat run overruns row".into()))?;
+            i += 1;
+            out.extend(std::iter::repeat_n(byte, count));
+        }
+        // control == -128 is a documented no-op.
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Pascal string, length byte + bytes, padded to a multiple of 4.
+    fn pascal_string(name: &str) -> Vec<u8> {
+        let mut out = vec![name.len() as u8];
+        out.extend_from_slice(name.as_bytes());
+        let padded_total = (name.len() + 1).div_ceil(4) * 4;
+        out.resize(padded_total, 0);
+        out
+    }
+
+    /// Builds a minimal, valid single-layer PSD: an 8-bit RGBA canvas with
+    /// one `width x height` layer, raw (uncompressed) channel data, no color
+    /// mode data or image resources.
+    fn minimal_psd(width: u32, height: u32, rgba: &[[u8; 4]]) -> Vec<u8> {
+        assert_eq!(rgba.len(), (width * height) as usize);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"8BPS");
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&[0u8; 6]);
+        out.extend_from_slice(&4u16.to_be_bytes()); // channels
+        out.extend_from_slice(&height.to_be_bytes());
+        out.extend_from_slice(&width.to_be_bytes());
+        out.extend_from_slice(&8u16.to_be_bytes()); // depth
+        out.extend_from_slice(&3u16.to_be_bytes()); // color mode (RGB)
+        out.extend_from_slice(&0u32.to_be_bytes()); // color mode data len
+        out.extend_from_slice(&0u32.to_be_bytes()); // image resources len
+
+        let w = width as usize;
+        let h = height as usize;
+        let plane_len = 2 + w * h; // compression u16 + raw bytes
+
+        let mut layer_info = Vec::new();
+        layer_info.extend_from_slice(&1i16.to_be_bytes()); // layer count
+
+        layer_info.extend_from_slice(&0i32.to_be_bytes()); // top
+        layer_info.extend_from_slice(&0i32.to_be_bytes()); // left
+        layer_info.extend_from_slice(&(height as i32).to_be_bytes()); // bottom
+        layer_info.extend_from_slice(&(width as i32).to_be_bytes()); // right
+
+        layer_info.extend_from_slice(&4u16.to_be_bytes()); // channel count
+        for id in [0i16, 1, 2, -1] {
+            layer_info.extend_from_slice(&id.to_be_bytes());
+            layer_info.extend_from_slice(&(plane_len as u32).to_be_bytes());
+        }
+
+        layer_info.extend_from_slice(b"8BIM");
+        layer_info.extend_from_slice(b"norm");
+        layer_info.push(255); // opacity
+        layer_info.push(0); // clipping
+        layer_info.push(0); // flags
+        layer_info.push(0); // filler
+
+        let name = pascal_string("layer");
+        let extra_data_len = 4 + 4 + name.len();
+        layer_info.extend_from_slice(&(extra_data_len as u32).to_be_bytes());
+        layer_info.extend_from_slice(&0u32.to_be_bytes()); // layer mask data len
+        layer_info.extend_from_slice(&0u32.to_be_bytes()); // blending ranges len
+        layer_info.extend_from_slice(&name);
+
+        for channel_idx in 0..4 {
+            layer_info.extend_from_slice(&0u16.to_be_bytes()); // compression = raw
+            for px in rgba {
+                layer_info.push(px[channel_idx]);
+            }
+        }
+
+        let mut layer_mask_info = Vec::new();
+        layer_mask_info.extend_from_slice(&(layer_info.len() as u32).to_be_bytes());
+        layer_mask_info.extend_from_slice(&layer_info);
+
+        out.extend_from_slice(&(layer_mask_info.len() as u32).to_be_bytes());
+        out.extend_from_slice(&layer_mask_info);
+
+        out
+    }
+
+    /// Like `minimal_psd`, but with an `iOpa` additional layer info block
+    /// (fill opacity) tacked on after the layer name.
+    fn minimal_psd_with_fill_opacity(width: u32, height: u32, rgba: &[[u8; 4]], fill_opacity: u8) -> Vec<u8> {
+        assert_eq!(rgba.len(), (width * height) as usize);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"8BPS");
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&[0u8; 6]);
+        out.extend_from_slice(&4u16.to_be_bytes());
+        out.extend_from_slice(&height.to_be_bytes());
+        out.extend_from_slice(&width.to_be_bytes());
+        out.extend_from_slice(&8u16.to_be_bytes());
+        out.extend_from_slice(&3u16.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+
+        let w = width as usize;
+        let h = height as usize;
+        let plane_len = 2 + w * h;
+
+        let mut layer_info = Vec::new();
+        layer_info.extend_from_slice(&1i16.to_be_bytes());
+
+        layer_info.extend_from_slice(&0i32.to_be_bytes());
+        layer_info.extend_from_slice(&0i32.to_be_bytes());
+        layer_info.extend_from_slice(&(height as i32).to_be_bytes());
+        layer_info.extend_from_slice(&(width as i32).to_be_bytes());
+
+        layer_info.extend_from_slice(&4u16.to_be_bytes());
+        for id in [0i16, 1, 2, -1] {
+            layer_info.extend_from_slice(&id.to_be_bytes());
+            layer_info.extend_from_slice(&(plane_len as u32).to_be_bytes());
+        }
+
+        layer_info.extend_from_slice(b"8BIM");
+        layer_info.extend_from_slice(b"norm");
+        layer_info.push(255); // opacity
+        layer_info.push(0);
+        layer_info.push(0);
+        layer_info.push(0);
+
+        let name = pascal_string("layer");
+        // One additional layer info block: "8BIM" + "iOpa" + len(4) + the
+        // fill opacity byte, padded to an even length (4, already even).
+        let iopa_block_len = 4 + 4 + 4 + 4;
+        let extra_data_len = 4 + 4 + name.len() + iopa_block_len;
+        layer_info.extend_from_slice(&(extra_data_len as u32).to_be_bytes());
+        layer_info.extend_from_slice(&0u32.to_be_bytes());
+        layer_info.extend_from_slice(&0u32.to_be_bytes());
+        layer_info.extend_from_slice(&name);
+
+        layer_info.extend_from_slice(b"8BIM");
+        layer_info.extend_from_slice(b"iOpa");
+        layer_info.extend_from_slice(&4u32.to_be_bytes());
+        layer_info.push(fill_opacity);
+        layer_info.extend_from_slice(&[0u8; 3]);
+
+        for channel_idx in 0..4 {
+            layer_info.extend_from_slice(&0u16.to_be_bytes());
+            for px in rgba {
+                layer_info.push(px[channel_idx]);
+            }
+        }
+
+        let mut layer_mask_info = Vec::new();
+        layer_mask_info.extend_from_slice(&(layer_info.len() as u32).to_be_bytes());
+        layer_mask_info.extend_from_slice(&layer_info);
+
+        out.extend_from_slice(&(layer_mask_info.len() as u32).to_be_bytes());
+        out.extend_from_slice(&layer_mask_info);
+
+        out
+    }
+
+    #[test]
+    fn parses_fill_opacity_from_the_iopa_block() {
+        let bytes = minimal_psd_with_fill_opacity(1, 1, &[[10, 20, 30, 255]], 128);
+
+        let psd = parse(&bytes).unwrap();
+
+        assert_eq!(psd.layers[0].opacity, 255);
+        assert_eq!(psd.layers[0].fill_opacity, 128);
+    }
+
+    #[test]
+    fn defaults_fill_opacity_to_opaque_when_no_iopa_block_is_present() {
+        let bytes = minimal_psd(1, 1, &[[10, 20, 30, 255]]);
+
+        let psd = parse(&bytes).unwrap();
+
+        assert_eq!(psd.layers[0].fill_opacity, 255);
+    }
+
+    #[test]
+    fn ignores_an_additional_info_block_whose_length_overruns_its_own_region() {
+        // A well-formed "8BIM"+"iOpa" header, but with a claimed length that
+        // would read past `extra_data_end` into whatever follows (the next
+        // layer record or the pixel data). The scan must stop instead of
+        // trusting that length, so the bogus block is simply ignored.
+        let mut bytes = minimal_psd_with_fill_opacity(1, 1, &[[10, 20, 30, 255]], 128);
+
+        let iopa_len_pos = bytes.len()
+            - (2 + 1) * 4 // four channels' worth of (compression + one pixel) data
+            - 4 // the iOpa block's 3 padding + 1 data byte
+            - 4; // the iOpa block's own length field
+        assert_eq!(&bytes[iopa_len_pos - 4..iopa_len_pos], b"iOpa");
+        bytes[iopa_len_pos..iopa_len_pos + 4].copy_from_slice(&0xffff_ffffu32.to_be_bytes());
+
+        let psd = parse(&bytes).unwrap();
+
+        assert_eq!(psd.layers[0].fill_opacity, 255);
+    }
+
+    #[test]
+    fn round_trips_a_minimal_single_layer_psd() {
+        let rgba = [[10, 20, 30, 255], [40, 50, 60, 128], [70, 80, 90, 0], [1, 2, 3, 4]];
+        let bytes = minimal_psd(2, 2, &rgba);
+
+        let psd = parse(&bytes).unwrap();
+
+        assert_eq!(psd.width, 2);
+        assert_eq!(psd.height, 2);
+        assert_eq!(psd.layers.len(), 1);
+
+        let layer = &psd.layers[0];
+        assert_eq!(layer.name, "layer");
+        assert_eq!(layer.opacity, 255);
+        assert_eq!(layer.blend_key, *b"norm");
+        assert_eq!(layer.rgba, rgba.concat());
+    }
+
+    #[test]
+    fn rejects_a_missing_signature() {
+        let mut bytes = minimal_psd(1, 1, &[[0, 0, 0, 0]]);
+        bytes[0] = b'X';
+
+        assert!(matches!(parse(&bytes), Err(PsdError::Parse(_))));
+    }
+
+    #[test]
+    fn rejects_an_unsupported_version() {
+        let mut bytes = minimal_psd(1, 1, &[[0, 0, 0, 0]]);
+        bytes[4..6].copy_from_slice(&2u16.to_be_bytes());
+
+        assert!(matches!(parse(&bytes), Err(PsdError::Parse(_))));
+    }
+
+    #[test]
+    fn rejects_truncated_input_instead_of_panicking() {
+        let bytes = minimal_psd(2, 2, &[[1, 2, 3, 4]; 4]);
+
+        for end in 0..bytes.len() {
+            // Every truncation cuts off at least part of the last channel's
+            // pixel data, so it must return a `Parse` error - never panic.
+            assert!(
+                matches!(parse(&bytes[..end]), Err(PsdError::Parse(_))),
+                "truncating at {} bytes should be rejected",
+                end
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_channel_len_too_short_for_its_own_compression_header() {
+        // `minimal_psd` always gives every channel a correct `len`, so build
+        // the layer info by hand here with the first channel's `len`
+        // corrupted to `1` - too short to even hold the compression u16
+        // that's supposed to follow it.
+        let mut out = Vec::new();
+        out.extend_from_slice(b"8BPS");
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&[0u8; 6]);
+        out.extend_from_slice(&4u16.to_be_bytes());
+        out.extend_from_slice(&1u32.to_be_bytes());
+        out.extend_from_slice(&1u32.to_be_bytes());
+        out.extend_from_slice(&8u16.to_be_bytes());
+        out.extend_from_slice(&3u16.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+
+        let mut layer_info = Vec::new();
+        layer_info.extend_from_slice(&1i16.to_be_bytes());
+        layer_info.extend_from_slice(&0i32.to_be_bytes());
+        layer_info.extend_from_slice(&0i32.to_be_bytes());
+        layer_info.extend_from_slice(&1i32.to_be_bytes());
+        layer_info.extend_from_slice(&1i32.to_be_bytes());
+        layer_info.extend_from_slice(&4u16.to_be_bytes());
+        // First channel (id 0) declares a len of 1: too short to hold even
+        // the 2-byte compression field that's supposed to follow.
+        layer_info.extend_from_slice(&0i16.to_be_bytes());
+        layer_info.extend_from_slice(&1u32.to_be_bytes());
+        for id in [1i16, 2, -1] {
+            layer_info.extend_from_slice(&id.to_be_bytes());
+            layer_info.extend_from_slice(&3u32.to_be_bytes());
+        }
+        layer_info.extend_from_slice(b"8BIM");
+        layer_info.extend_from_slice(b"norm");
+        layer_info.push(255);
+        layer_info.push(0);
+        layer_info.push(0);
+        layer_info.push(0);
+        let name = pascal_string("layer");
+        let extra_data_len = 4 + 4 + name.len();
+        layer_info.extend_from_slice(&(extra_data_len as u32).to_be_bytes());
+        layer_info.extend_from_slice(&0u32.to_be_bytes());
+        layer_info.extend_from_slice(&0u32.to_be_bytes());
+        layer_info.extend_from_slice(&name);
+        // No pixel data is needed; decoding the first channel must fail
+        // before it ever reads this far.
+
+        let mut layer_mask_info = Vec::new();
+        layer_mask_info.extend_from_slice(&(layer_info.len() as u32).to_be_bytes());
+        layer_mask_info.extend_from_slice(&layer_info);
+        out.extend_from_slice(&(layer_mask_info.len() as u32).to_be_bytes());
+        out.extend_from_slice(&layer_mask_info);
+
+        match parse(&out) {
+            Err(PsdError::Parse(_)) => {}
+            other => panic!("expected a Parse error, got {:?}", other),
+        }
+    }
+}